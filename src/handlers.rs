@@ -1,8 +1,14 @@
+// `UserError` implements `middleware::ResponseError` (see `models.rs`),
+// mapping each variant to a status code and message. Nothing in this repo
+// calls it yet: turning an `Err` branch into that JSON body automatically
+// needs the `#[handler]` codegen itself to call it, which lives in darpi's
+// proc-macro crate and isn't part of this example. This trait/impl is a
+// stub ready for that codegen to pick up, not something already wired in.
 use super::{Container, DbPoolGetter};
-use crate::middleware::{roundtrip, Role};
+use crate::middleware::{roundtrip, Role, TokenTtl};
 use crate::models::{self, NewUser, User, UserError};
 use darpi::job::IOBlockingJob;
-use darpi::{chrono::Duration, handler, Json, Path, Query};
+use darpi::{handler, Json, Path, Query};
 use darpi_middleware::{auth::*, body_size_limit};
 use log::warn;
 use serde::{Deserialize, Serialize};
@@ -14,27 +20,111 @@ pub struct Login {
     password: String,
 }
 
+// a token pair returned to the client: `access_token` is the short-lived
+// bearer token minted by `JwtTokenCreator`, `refresh_token` is the opaque,
+// server-side-stored id tracked in the `refresh_tokens` table (see
+// `models::create_refresh_token`/`models::rotate_refresh_token`)
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TokenPair {
+    access_token: Token,
+    refresh_token: String,
+}
+
 // here we give the container type
 // so the framework knows where to get
-// the requested `Arc<dyn JwtTokenCreator>` from
+// the requested `Arc<dyn JwtTokenCreator>` and `Arc<dyn DbPoolGetter>` from
+//
+// alongside the access token, this stores an opaque refresh token
+// server-side so clients don't have to be handed a 30-day bearer token
+// that can never be revoked
 #[handler({
     container: Container
 })]
 pub(crate) async fn login(
-    #[body] _login_data: Json<Login>,
+    #[body] login_data: Json<Login>,
     #[inject] jwt_tok_creator: Arc<dyn JwtTokenCreator>,
-) -> Result<Token, Error> {
-    let admin = Role::Admin; // hardcoded just for the example
-    let uid = "uid"; // hardcoded just for the example
-    let tok = jwt_tok_creator
-        .create(uid, &admin, Duration::days(30))
+    #[inject] db_pool: Arc<dyn DbPoolGetter>,
+) -> Result<TokenPair, UserError> {
+    let login_data = login_data.into_inner();
+    let conn = db_pool.pool().get()?;
+
+    //diesel does not have an async api and argon2 verification is
+    //deliberately slow, so we don't want to block the server thread
+    //we offload this as a blocking task to be executed on an appropriate
+    //thread and wait for the result on an async channel, same as `create_user`
+    let job = move || models::verify_credentials(&login_data.email, &login_data.password, &conn);
+    let user = darpi::oneshot(IOBlockingJob::from(job))
+        .await
+        .map_err(|_| UserError::InternalError)?
+        .await
+        .map_err(|_| UserError::InternalError)??;
+
+    // no explicit override: the configured default lifetimes apply
+    let ttl = TokenTtl::from_env_or_default();
+    let access_token = jwt_tok_creator
+        .create(&user.id.to_string(), &user.role, ttl.access)
         .await
         .map_err(|e| {
             warn!("could not create a token: {}", e);
-            e
+            UserError::InternalError
         })?;
 
-    Ok(tok)
+    let conn = db_pool.pool().get()?;
+    let user_id = user.id;
+    let role = user.role;
+    let job = move || models::create_refresh_token(user_id, role, ttl.refresh, &conn);
+    let refresh_token = darpi::oneshot(IOBlockingJob::from(job))
+        .await
+        .map_err(|_| UserError::InternalError)?
+        .await
+        .map_err(|_| UserError::InternalError)??;
+
+    Ok(TokenPair {
+        access_token,
+        refresh_token,
+    })
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+// exchanges a still-valid refresh token for a new access token and rotates
+// the refresh token itself in the same DB transaction
+// (`models::rotate_refresh_token`), so a replayed (already-used) token is
+// rejected instead of silently accepted
+#[handler({
+    container: Container
+})]
+pub(crate) async fn refresh(
+    #[body] refresh_data: Json<RefreshRequest>,
+    #[inject] jwt_tok_creator: Arc<dyn JwtTokenCreator>,
+    #[inject] db_pool: Arc<dyn DbPoolGetter>,
+) -> Result<TokenPair, UserError> {
+    let conn = db_pool.pool().get()?;
+    let old_token = refresh_data.into_inner().refresh_token;
+    let ttl = TokenTtl::from_env_or_default();
+
+    let job = move || models::rotate_refresh_token(&old_token, ttl.refresh, &conn);
+    let rotated = darpi::oneshot(IOBlockingJob::from(job))
+        .await
+        .map_err(|_| UserError::InternalError)?
+        .await
+        .map_err(|_| UserError::InternalError)??;
+
+    let access_token = jwt_tok_creator
+        .create(&rotated.user_id.to_string(), &rotated.role, ttl.access)
+        .await
+        .map_err(|e| {
+            warn!("could not create a token: {}", e);
+            UserError::InternalError
+        })?;
+
+    Ok(TokenPair {
+        access_token,
+        refresh_token: rotated.new_refresh_token,
+    })
 }
 
 #[derive(Deserialize, Serialize, Debug, Query, Path)]
@@ -47,6 +137,92 @@ pub(crate) async fn home() -> String {
     "Welcome to darpi".to_string()
 }
 
+// hand-maintained for now: deriving this from each `#[handler]`'s route,
+// body/path/query types and `authorize(...)` guard at macro-expansion time
+// needs a codegen pass in darpi's proc-macro crate, which isn't part of
+// this example; this covers every route registered in `main.rs`'s `app!`
+// handlers list except the docs routes themselves (`/openapi.json` and
+// `/swagger`)
+#[handler]
+pub(crate) async fn openapi_json() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "openapi": "3.0.0",
+        "info": { "title": "example-heroku-darpi", "version": "0.1.0" },
+        "paths": {
+            "/": {
+                "get": {}
+            },
+            "/login": {
+                "post": {
+                    "requestBody": {
+                        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Login" } } }
+                    }
+                }
+            },
+            "/refresh": {
+                "post": {
+                    "requestBody": {
+                        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/RefreshRequest" } } }
+                    }
+                }
+            },
+            "/hello_world/{name}": {
+                "get": {
+                    "parameters": [{ "name": "name", "in": "path", "required": true, "schema": { "type": "string" } }]
+                }
+            },
+            "/important": {
+                "post": {}
+            },
+            "/starwars": {
+                "post": {},
+                "get": {}
+            },
+            "/users": {
+                "post": {
+                    "security": [{ "bearerAuth": [] }],
+                    "requestBody": {
+                        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/NewUser" } } }
+                    }
+                }
+            },
+            "/users/{id}": {
+                "get": {
+                    "security": [{ "bearerAuth": [] }],
+                    "parameters": [{ "name": "id", "in": "path", "required": true, "schema": { "type": "integer" } }]
+                }
+            }
+        },
+        "components": {
+            "schemas": {
+                "Login": { "type": "object", "required": ["email", "password"], "properties": { "email": { "type": "string" }, "password": { "type": "string" } } },
+                "RefreshRequest": { "type": "object", "required": ["refresh_token"], "properties": { "refresh_token": { "type": "string" } } },
+                "NewUser": { "type": "object", "required": ["email", "password"], "properties": { "email": { "type": "string" }, "password": { "type": "string" } } }
+            },
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" }
+            }
+        }
+    }))
+}
+
+const SWAGGER_UI_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>example-heroku-darpi</title></head>
+<body>
+<div id="swagger-ui"></div>
+<script src="https://unpkg.com/swagger-ui-dist/swagger-ui-bundle.js"></script>
+<script>
+  window.onload = () => SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+</script>
+</body>
+</html>"#;
+
+#[handler]
+pub(crate) async fn swagger_ui() -> String {
+    SWAGGER_UI_HTML.to_string()
+}
+
 // here we give the container type
 // so the framework knows where to get
 // the requested `Arc<dyn DbPoolGetter>` from
@@ -89,8 +265,14 @@ pub struct UserID {
 // here we give the container type
 // so the framework knows where to get
 // the requested `Arc<dyn DbPoolGetter>` from
+// `authorize` compares the JWT's role claim against `Role`'s derived `Ord`,
+// so `authorize(Role::Editor)` admits editors and admins alike
+// (`Role::Admin >= Role::Editor`) without duplicating this handler
 #[handler({
-    container: Container
+    container: Container,
+    middleware: {
+        request: [authorize(Role::Editor)]
+    }
 })]
 pub(crate) async fn get_user(
     #[path] user_id: UserID,
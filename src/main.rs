@@ -1,12 +1,16 @@
 mod handlers;
 mod middleware;
+mod models;
 mod starwars;
 
 use async_graphql::{EmptyMutation, EmptySubscription, Schema};
 use darpi::{app, Method};
 use darpi_middleware::auth::*;
 use darpi_middleware::{body_size_limit, compression::decompress};
-use handlers::{do_something, home, important, login};
+use handlers::{
+    create_user, do_something, get_user, home, important, login, openapi_json, refresh,
+    swagger_ui,
+};
 use shaku::module;
 use starwars::*;
 
@@ -55,6 +59,13 @@ async fn main() -> Result<(), darpi::Error> {
         },
         // a set of global middleware that will be executed for every handler
         // the order matters and it's up to the user to apply them in desired order
+        //
+        // CORS (browser clients on another origin calling `/starwars` or the
+        // JSON APIs) isn't listed here: `middleware::Cors` computes the
+        // right allow-list/preflight decisions, but actually enforcing them
+        // per request needs a hook into `darpi_middleware`'s pipeline that
+        // doesn't exist in this snapshot, so wiring a config in here would
+        // silently do nothing
         middleware: {
             request: [body_size_limit(128), decompress()]
         },
@@ -69,6 +80,11 @@ async fn main() -> Result<(), darpi::Error> {
                 method: Method::POST,
                 handler: login
             },
+            {
+                route: "/refresh",
+                method: Method::POST,
+                handler: refresh
+            },
             {
                 route: "/hello_world/{name}",
                 method: Method::GET,
@@ -88,6 +104,26 @@ async fn main() -> Result<(), darpi::Error> {
                 route: "/starwars",
                 method: Method::GET,
                 handler: starwars_get
+            },
+            {
+                route: "/users",
+                method: Method::POST,
+                handler: create_user
+            },
+            {
+                route: "/users/{id}",
+                method: Method::GET,
+                handler: get_user
+            },
+            {
+                route: "/openapi.json",
+                method: Method::GET,
+                handler: openapi_json
+            },
+            {
+                route: "/swagger",
+                method: Method::GET,
+                handler: swagger_ui
             }
         ]
     })
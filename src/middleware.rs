@@ -0,0 +1,257 @@
+use darpi::chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The example app's own notion of a user role, as embedded in the JWT
+/// claims `JwtTokenCreator` signs. Kept local (rather than in
+/// `darpi_middleware`) since the set of roles is app-specific.
+///
+/// Ranked `Admin > Editor > User` (derive order is ascending, so `Admin`
+/// is declared last): `Role::Admin > Role::Editor` holds, so
+/// `darpi_middleware::auth::authorize(Role::Editor)` compares the JWT's role
+/// claim against this `Ord` and also admits `Role::Admin`, letting
+/// `get_user` stay gated at `Editor` without duplicating a handler for
+/// `Admin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Role {
+    User,
+    Editor,
+    Admin,
+}
+
+impl From<i32> for Role {
+    fn from(v: i32) -> Self {
+        match v {
+            2 => Role::Admin,
+            1 => Role::Editor,
+            _ => Role::User,
+        }
+    }
+}
+
+impl From<Role> for i32 {
+    fn from(r: Role) -> Self {
+        match r {
+            Role::User => 0,
+            Role::Editor => 1,
+            Role::Admin => 2,
+        }
+    }
+}
+
+/// maps a domain error to the status code and message a `{ "status": ...,
+/// "message": ... }` JSON body would carry for a handler's `Err` branch.
+/// Turning that into an automatic response on every `Err` still needs the
+/// `#[handler]` codegen itself to call it, which lives in darpi's
+/// proc-macro crate (not part of this example) — this trait and its impls
+/// are the mapping that codegen would call, not a wired-in response path.
+pub trait ResponseError {
+    fn status_code(&self) -> u16;
+    fn message(&self) -> String;
+}
+
+// `darpi_middleware::auth::Error`'s variants aren't visible from this crate,
+// so rejections from `authorize(...)` (missing/invalid/expired token) are
+// all mapped to 401 here rather than the finer 400/403 split a look at its
+// variants could give
+impl ResponseError for darpi_middleware::auth::Error {
+    fn status_code(&self) -> u16 {
+        401
+    }
+
+    fn message(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// The allow-list/header computation for CORS: given a request's method and
+/// `Origin`, decides whether it's a preflight and what
+/// `Access-Control-Allow-*` headers a response should carry. This is pure,
+/// request-independent logic (it takes plain strings, not the actual
+/// request/response), because actually short-circuiting an `OPTIONS`
+/// request and writing headers onto every response needs a hook into
+/// `darpi_middleware`'s own request/response pipeline, which lives outside
+/// this repo — wiring that up is a stub pending upstream support, not
+/// something this crate can add to `darpi_middleware` from here. Call
+/// `is_preflight`/`allow_origin_header`/etc. directly once that hook exists.
+pub struct Cors {
+    allow_origins: Vec<String>,
+    allow_methods: Vec<String>,
+    allow_headers: Vec<String>,
+    allow_credentials: bool,
+    max_age: u32,
+}
+
+pub struct CorsBuilder {
+    allow_origins: Vec<String>,
+    allow_methods: Vec<String>,
+    allow_headers: Vec<String>,
+    allow_credentials: bool,
+    max_age: u32,
+}
+
+impl Cors {
+    pub fn build() -> CorsBuilder {
+        CorsBuilder {
+            allow_origins: Vec::new(),
+            allow_methods: Vec::new(),
+            allow_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: 0,
+        }
+    }
+
+    /// `true` for a CORS preflight request: an `OPTIONS` method carrying an
+    /// `Access-Control-Request-Method` header
+    pub fn is_preflight(method: &str, has_request_method_header: bool) -> bool {
+        method.eq_ignore_ascii_case("OPTIONS") && has_request_method_header
+    }
+
+    pub fn is_origin_allowed(&self, origin: &str) -> bool {
+        self.allow_origins.iter().any(|o| o == "*" || o == origin)
+    }
+
+    /// the `Access-Control-Allow-Origin` value for `origin`, or `None` if
+    /// it isn't on the allow-list
+    pub fn allow_origin_header(&self, origin: &str) -> Option<String> {
+        if self.is_origin_allowed(origin) {
+            Some(origin.to_string())
+        } else {
+            None
+        }
+    }
+
+    pub fn allow_methods_header(&self) -> String {
+        self.allow_methods.join(", ")
+    }
+
+    pub fn allow_headers_header(&self) -> String {
+        self.allow_headers.join(", ")
+    }
+
+    pub fn allow_credentials(&self) -> bool {
+        self.allow_credentials
+    }
+
+    pub fn max_age(&self) -> u32 {
+        self.max_age
+    }
+}
+
+impl CorsBuilder {
+    pub fn allow_origins<I, S>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allow_origins = origins.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn allow_methods<I, S>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allow_methods = methods.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn allow_headers<I, S>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allow_headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = allow;
+        self
+    }
+
+    pub fn max_age(mut self, seconds: u32) -> Self {
+        self.max_age = seconds;
+        self
+    }
+
+    pub fn finish(self) -> Cors {
+        Cors {
+            allow_origins: self.allow_origins,
+            allow_methods: self.allow_methods,
+            allow_headers: self.allow_headers,
+            allow_credentials: self.allow_credentials,
+            max_age: self.max_age,
+        }
+    }
+}
+
+// a toy per-handler request middleware used by `create_user`'s example
+// wiring: it returns the configured string unchanged, demonstrating state
+// handed into `#[middleware::request(0)]`
+pub async fn roundtrip(s: &'static str) -> Result<String, std::convert::Infallible> {
+    Ok(s.to_string())
+}
+
+#[derive(Debug)]
+pub struct ParseDurationError(String);
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid duration `{}`, expected e.g. \"30d\", \"15m\" or \"2h\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+/// parses humantime-style strings like `"30d"`, `"15m"` or `"2h"` into a
+/// `chrono::Duration`, so access/refresh token lifetimes can be configured
+/// from environment/config without recompiling
+pub fn parse_duration(input: &str) -> Result<Duration, ParseDurationError> {
+    let input = input.trim();
+    let split_at = input
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| ParseDurationError(input.to_string()))?;
+    let (amount, unit) = input.split_at(split_at);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| ParseDurationError(input.to_string()))?;
+
+    match unit {
+        "s" => Ok(Duration::seconds(amount)),
+        "m" => Ok(Duration::minutes(amount)),
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        _ => Err(ParseDurationError(input.to_string())),
+    }
+}
+
+/// the configured access/refresh token lifetimes, read from the environment
+/// each time `from_env_or_default` is called (`login` and `refresh` call it
+/// on every request)
+pub struct TokenTtl {
+    pub access: Duration,
+    pub refresh: Duration,
+}
+
+impl TokenTtl {
+    /// reads `ACCESS_TOKEN_TTL`/`REFRESH_TOKEN_TTL`, falling back to a
+    /// short-lived access token and a long-lived refresh token if unset
+    pub fn from_env_or_default() -> Self {
+        let access = std::env::var("ACCESS_TOKEN_TTL")
+            .ok()
+            .and_then(|s| parse_duration(&s).ok())
+            .unwrap_or_else(|| Duration::minutes(15));
+        let refresh = std::env::var("REFRESH_TOKEN_TTL")
+            .ok()
+            .and_then(|s| parse_duration(&s).ok())
+            .unwrap_or_else(|| Duration::days(30));
+
+        Self { access, refresh }
+    }
+}
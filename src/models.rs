@@ -0,0 +1,269 @@
+use crate::middleware::Role;
+use diesel::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+table! {
+    refresh_tokens (id) {
+        id -> Text,
+        user_id -> Integer,
+        role -> Integer,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    users (id) {
+        id -> Integer,
+        email -> Text,
+        password_hash -> Text,
+        role -> Integer,
+        blocked -> Bool,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct User {
+    pub id: i32,
+    pub email: String,
+    pub role: Role,
+    pub blocked: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewUser {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Queryable)]
+struct UserRow {
+    id: i32,
+    email: String,
+    password_hash: String,
+    role: i32,
+    blocked: bool,
+}
+
+impl From<UserRow> for User {
+    fn from(row: UserRow) -> Self {
+        User {
+            id: row.id,
+            email: row.email,
+            role: row.role.into(),
+            blocked: row.blocked,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum UserError {
+    InternalError,
+    InvalidRefreshToken,
+    BlockedUser,
+    InvalidPassword,
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserError::InternalError => write!(f, "internal error"),
+            UserError::InvalidRefreshToken => write!(f, "invalid or expired refresh token"),
+            UserError::BlockedUser => write!(f, "user is blocked"),
+            UserError::InvalidPassword => write!(f, "invalid email or password"),
+        }
+    }
+}
+
+impl std::error::Error for UserError {}
+
+// missing/invalid credentials -> 401, blocked account -> 403, everything
+// else that isn't the caller's fault -> 500
+impl crate::middleware::ResponseError for UserError {
+    fn status_code(&self) -> u16 {
+        match self {
+            UserError::InternalError => 500,
+            UserError::InvalidRefreshToken => 401,
+            UserError::BlockedUser => 403,
+            UserError::InvalidPassword => 401,
+        }
+    }
+
+    fn message(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl From<diesel::result::Error> for UserError {
+    fn from(_: diesel::result::Error) -> Self {
+        UserError::InternalError
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for UserError {
+    fn from(_: diesel::r2d2::PoolError) -> Self {
+        UserError::InternalError
+    }
+}
+
+#[derive(Queryable)]
+struct RefreshTokenRow {
+    id: String,
+    user_id: i32,
+    role: i32,
+    expires_at: chrono::NaiveDateTime,
+}
+
+pub struct RotatedRefreshToken {
+    pub user_id: i32,
+    pub role: Role,
+    pub new_refresh_token: String,
+}
+
+fn generate_opaque_id() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD)
+}
+
+pub fn create_user(new_user: NewUser, conn: &diesel::PgConnection) -> Result<User, UserError> {
+    use self::users::dsl::*;
+
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill(&mut salt);
+    let hash = argon2::hash_encoded(new_user.password.as_bytes(), &salt, &argon2::Config::default())
+        .map_err(|_| UserError::InternalError)?;
+
+    let row: UserRow = diesel::insert_into(users)
+        .values((
+            email.eq(&new_user.email),
+            password_hash.eq(&hash),
+            role.eq(i32::from(Role::User)),
+            blocked.eq(false),
+        ))
+        .get_result(conn)?;
+
+    Ok(row.into())
+}
+
+pub fn find_user_by_id(id_in: i32, conn: &diesel::PgConnection) -> Result<Option<User>, UserError> {
+    use self::users::dsl::*;
+
+    let row: Option<UserRow> = users.find(id_in).first(conn).optional()?;
+    Ok(row.map(Into::into))
+}
+
+// a fixed password/salt pair hashed on every lookup, found row or not, and
+// verified against when no row is found, so an unknown email pays the same
+// Argon2 cost as a real one instead of returning early and leaking which
+// emails are registered (or blocked) via latency
+const DUMMY_PASSWORD: &[u8] = b"constant-time-dummy-password";
+const DUMMY_SALT: [u8; 16] = [0u8; 16];
+
+fn dummy_password_hash() -> String {
+    argon2::hash_encoded(DUMMY_PASSWORD, &DUMMY_SALT, &argon2::Config::default())
+        .expect("hashing a fixed dummy password never fails")
+}
+
+/// looks the user up by email, then verifies the submitted password against
+/// the stored Argon2/PHC-encoded hash (or, if the email isn't registered,
+/// against a dummy hash) before inspecting `blocked` or deciding the final
+/// error, so an unknown email, a blocked account, and a wrong password all
+/// pay the same Argon2 cost and can't be told apart by response latency
+pub fn verify_credentials(
+    email_in: &str,
+    password_in: &str,
+    conn: &diesel::PgConnection,
+) -> Result<User, UserError> {
+    use self::users::dsl::*;
+
+    let row: Option<UserRow> = users
+        .filter(email.eq(email_in))
+        .first(conn)
+        .optional()?;
+
+    let dummy_hash = dummy_password_hash();
+    let hash_to_check = row
+        .as_ref()
+        .map(|r| r.password_hash.as_str())
+        .unwrap_or(&dummy_hash);
+    let matches = argon2::verify_encoded(hash_to_check, password_in.as_bytes())
+        .map_err(|_| UserError::InternalError)?;
+
+    let row = row.ok_or(UserError::InvalidPassword)?;
+    if row.blocked {
+        return Err(UserError::BlockedUser);
+    }
+    if !matches {
+        return Err(UserError::InvalidPassword);
+    }
+
+    Ok(row.into())
+}
+
+/// inserts a fresh, opaque refresh-token row for `user_id_in`, expiring at
+/// `now + ttl`
+pub fn create_refresh_token(
+    user_id_in: i32,
+    role_in: Role,
+    ttl: chrono::Duration,
+    conn: &diesel::PgConnection,
+) -> Result<String, UserError> {
+    use self::refresh_tokens::dsl::*;
+
+    let tok = generate_opaque_id();
+    let expiry = chrono::Utc::now().naive_utc() + ttl;
+
+    diesel::insert_into(refresh_tokens)
+        .values((
+            id.eq(&tok),
+            user_id.eq(user_id_in),
+            role.eq(i32::from(role_in)),
+            expires_at.eq(expiry),
+        ))
+        .execute(conn)?;
+
+    Ok(tok)
+}
+
+/// validates `old_token`, deletes it, and inserts its replacement in the
+/// same transaction, so a second use of `old_token` (replay) finds no
+/// matching row and is rejected with `InvalidRefreshToken`
+pub fn rotate_refresh_token(
+    old_token: &str,
+    new_ttl: chrono::Duration,
+    conn: &diesel::PgConnection,
+) -> Result<RotatedRefreshToken, UserError> {
+    use self::refresh_tokens::dsl::*;
+
+    conn.transaction(|| {
+        let row: Option<RefreshTokenRow> = refresh_tokens
+            .filter(id.eq(old_token))
+            .first(conn)
+            .optional()?;
+        let row = row.ok_or(UserError::InvalidRefreshToken)?;
+
+        diesel::delete(refresh_tokens.filter(id.eq(old_token))).execute(conn)?;
+
+        if row.expires_at < chrono::Utc::now().naive_utc() {
+            return Err(UserError::InvalidRefreshToken);
+        }
+
+        let new_token = generate_opaque_id();
+        let expiry = chrono::Utc::now().naive_utc() + new_ttl;
+        diesel::insert_into(refresh_tokens)
+            .values((
+                id.eq(&new_token),
+                user_id.eq(row.user_id),
+                role.eq(row.role),
+                expires_at.eq(expiry),
+            ))
+            .execute(conn)?;
+
+        Ok(RotatedRefreshToken {
+            user_id: row.user_id,
+            role: row.role.into(),
+            new_refresh_token: new_token,
+        })
+    })
+}